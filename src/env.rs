@@ -1,9 +1,16 @@
 pub mod environment {
     use regex::Regex;
     use std::env;
+    use std::time::{SystemTime, UNIX_EPOCH};
 
     const SEMVAR_REGEX: &str = r"^([0-9]+)(\.[0-9]+)?(\.[0-9]+)?";
     const INVALID_REDIS_VERSION_MSG: &str = "Invalid value for REDIS_VERSION set in env";
+    const INVALID_REDIS_BITS_MSG: &str = "Invalid value for REDIS_BITS set in env";
+    const INVALID_USED_MEM_MSG: &str = "Invalid value for REDIS_USED_MEM set in env";
+    const INVALID_CTIME_MSG: &str = "Invalid value for REDIS_CTIME set in env";
+
+    const DEFAULT_REDIS_BITS: u8 = 64;
+    const DEFAULT_USED_MEM: u64 = 0;
 
     fn get_major_version(semantic_version: &str) -> u8 {
         let semvar_regex = Regex::new(SEMVAR_REGEX).unwrap();
@@ -24,4 +31,125 @@ pub mod environment {
         };
         get_major_version(&redis_version)
     }
+
+    // The full semver string reported in the RDB `redis-ver` aux field, as
+    // opposed to `get_redis_version` which only extracts the major version
+    // `header` needs for the `REDISNNNN` magic. Missing minor/patch
+    // components (e.g. a bare `REDIS_VERSION=7`) are zero-filled so the
+    // aux field always renders as a full `major.minor.patch` string.
+    pub fn get_redis_version_string() -> String {
+        let redis_version = match env::var_os("REDIS_VERSION") {
+            Some(v) => v.into_string().unwrap(),
+            None => return format!("{}.0.0", crate::DEFAULT_REDIS_VERSION),
+        };
+
+        let semvar_regex = Regex::new(SEMVAR_REGEX).unwrap();
+        match semvar_regex.captures(&redis_version) {
+            Some(version) => {
+                let major = version.get(1).expect(INVALID_REDIS_VERSION_MSG).as_str();
+                let minor = version
+                    .get(2)
+                    .map_or("0", |m| m.as_str().trim_start_matches('.'));
+                let patch = version
+                    .get(3)
+                    .map_or("0", |m| m.as_str().trim_start_matches('.'));
+                format!("{major}.{minor}.{patch}")
+            }
+            None => format!("{}.0.0", crate::DEFAULT_REDIS_VERSION),
+        }
+    }
+
+    pub fn get_redis_bits() -> u8 {
+        match env::var_os("REDIS_BITS") {
+            Some(v) => v
+                .into_string()
+                .unwrap()
+                .parse::<u8>()
+                .expect(INVALID_REDIS_BITS_MSG),
+            None => DEFAULT_REDIS_BITS,
+        }
+    }
+
+    pub fn get_used_mem() -> u64 {
+        match env::var_os("REDIS_USED_MEM") {
+            Some(v) => v
+                .into_string()
+                .unwrap()
+                .parse::<u64>()
+                .expect(INVALID_USED_MEM_MSG),
+            None => DEFAULT_USED_MEM,
+        }
+    }
+
+    // Global fallback expiry, used for keys that don't carry their own
+    // `__expire_at__`/`__ttl_ms__` hint (see `rdb_writer::extract_expiry`).
+    pub fn get_default_expire_at() -> Option<u32> {
+        env::var_os("REDIS_DEFAULT_EXPIRE_AT").map(|v| {
+            v.into_string()
+                .unwrap()
+                .parse::<u32>()
+                .expect("Invalid value for REDIS_DEFAULT_EXPIRE_AT set in env")
+        })
+    }
+
+    pub fn get_default_ttl_ms() -> Option<u64> {
+        env::var_os("REDIS_DEFAULT_TTL_MS").map(|v| {
+            v.into_string()
+                .unwrap()
+                .parse::<u64>()
+                .expect("Invalid value for REDIS_DEFAULT_TTL_MS set in env")
+        })
+    }
+
+    // Thresholds controlling when a small hash/set is packed into a single
+    // compact blob (listpack/intset) instead of being written element by
+    // element. Named and defaulted after the real `redis.conf` knobs they
+    // mirror.
+    pub fn get_hash_max_listpack_entries() -> u64 {
+        get_env_u64_or("REDIS_HASH_MAX_LISTPACK_ENTRIES", 128)
+    }
+
+    pub fn get_hash_max_listpack_value() -> u64 {
+        get_env_u64_or("REDIS_HASH_MAX_LISTPACK_VALUE", 64)
+    }
+
+    pub fn get_set_max_intset_entries() -> u64 {
+        get_env_u64_or("REDIS_SET_MAX_INTSET_ENTRIES", 512)
+    }
+
+    fn get_env_u64_or(var_name: &str, default: u64) -> u64 {
+        match env::var_os(var_name) {
+            Some(v) => v
+                .into_string()
+                .unwrap()
+                .parse::<u64>()
+                .unwrap_or_else(|_| panic!("Invalid value for {var_name} set in env")),
+            None => default,
+        }
+    }
+
+    pub fn now_unix_millis() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect(INVALID_CTIME_MSG)
+            .as_millis()
+            .try_into()
+            .expect(INVALID_CTIME_MSG)
+    }
+
+    pub fn get_ctime() -> u32 {
+        match env::var_os("REDIS_CTIME") {
+            Some(v) => v
+                .into_string()
+                .unwrap()
+                .parse::<u32>()
+                .expect(INVALID_CTIME_MSG),
+            None => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect(INVALID_CTIME_MSG)
+                .as_secs()
+                .try_into()
+                .expect(INVALID_CTIME_MSG),
+        }
+    }
 }
@@ -1,4 +1,6 @@
 use std::io;
+use std::io::Read;
+use std::process::ExitCode;
 
 mod env;
 mod rdb;
@@ -8,11 +10,14 @@ use clap::Parser;
 use env::environment;
 use flate2::read::GzDecoder;
 use once_cell::sync::OnceCell;
-use rdb::rdb_writer;
+use rdb::{rdb_reader, rdb_writer};
 const DEFAULT_REDIS_VERSION: u8 = 7;
 const INVALID_TOML_ERROR: &str = "Invalid TOML for Redis";
 
 static REDIS_VERSION: OnceCell<u8> = OnceCell::new();
+static REDIS_BITS: OnceCell<u8> = OnceCell::new();
+static REDIS_USED_MEM: OnceCell<u64> = OnceCell::new();
+static REDIS_CTIME: OnceCell<u32> = OnceCell::new();
 
 #[derive(Parser, Debug)]
 #[command(about = "rdbdump - CLI to stream a TOML file into rdb format")]
@@ -24,19 +29,88 @@ struct Args {
         default_value_t = false
     )]
     gzipped: bool,
+
+    #[arg(
+        long = "redis-bits",
+        help = "Value of the `redis-bits` RDB aux field (default: 64, or $REDIS_BITS)",
+        default_value_t = environment::get_redis_bits()
+    )]
+    redis_bits: u8,
+
+    #[arg(
+        long = "used-mem",
+        help = "Value of the `used-mem` RDB aux field (default: 0, or $REDIS_USED_MEM)",
+        default_value_t = environment::get_used_mem()
+    )]
+    used_mem: u64,
+
+    #[arg(
+        long = "ctime",
+        help = "Value of the `ctime` RDB aux field (default: now, or $REDIS_CTIME)",
+        default_value_t = environment::get_ctime()
+    )]
+    ctime: u32,
+
+    #[arg(
+        long = "verify",
+        help = "Parse the generated RDB back and check it matches the input TOML, instead of writing the RDB to stdout",
+        default_value_t = false
+    )]
+    verify: bool,
 }
 
-fn main() -> Result<(), io::Error> {
+fn main() -> Result<ExitCode, io::Error> {
     REDIS_VERSION.set(environment::get_redis_version()).unwrap();
-    let mut stdout_buffer = io::BufWriter::new(io::stdout());
 
     let args = Args::parse();
+    REDIS_BITS.set(args.redis_bits).unwrap();
+    REDIS_USED_MEM.set(args.used_mem).unwrap();
+    REDIS_CTIME.set(args.ctime).unwrap();
+
+    let mut toml_source = String::new();
     if args.gzipped {
-        let mut stdin_buffer = io::BufReader::new(GzDecoder::new(io::stdin()));
-        let _ = rdb_writer::rdb_from_buffer(&mut stdin_buffer, &mut stdout_buffer);
+        GzDecoder::new(io::stdin()).read_to_string(&mut toml_source)?;
     } else {
-        let mut stdin_buffer = io::BufReader::new(io::stdin());
-        let _ = rdb_writer::rdb_from_buffer(&mut stdin_buffer, &mut stdout_buffer);
+        io::stdin().read_to_string(&mut toml_source)?;
+    }
+
+    if args.verify {
+        return Ok(verify(&toml_source));
+    }
+
+    let mut stdout_buffer = io::BufWriter::new(io::stdout());
+    let mut stdin_buffer = io::BufReader::new(toml_source.as_bytes());
+    let _ = rdb_writer::rdb_from_buffer(&mut stdin_buffer, &mut stdout_buffer);
+    Ok(ExitCode::SUCCESS)
+}
+
+// Round-trips `toml_source` through the writer and reader in memory and
+// reports whether the reconstructed keyspace matches the input, for the
+// `--verify` flag.
+fn verify(toml_source: &str) -> ExitCode {
+    let mut rdb_bytes = Vec::new();
+    let mut stdin_buffer = io::BufReader::new(toml_source.as_bytes());
+    if let Err(err) = rdb_writer::rdb_from_buffer(&mut stdin_buffer, &mut rdb_bytes) {
+        eprintln!("failed to write RDB: {err}");
+        return ExitCode::FAILURE;
+    }
+
+    let entries = match rdb_reader::parse(&rdb_bytes) {
+        Ok(entries) => entries,
+        Err(err) => {
+            eprintln!("failed to read back generated RDB: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match rdb_reader::verify_against_toml(toml_source, &entries) {
+        Ok(()) => {
+            println!("OK: RDB round-trip matches input TOML");
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("verification failed: {err}");
+            ExitCode::FAILURE
+        }
     }
-    Ok(())
 }
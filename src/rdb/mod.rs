@@ -0,0 +1,2 @@
+pub mod rdb_reader;
+pub mod rdb_writer;
@@ -0,0 +1,466 @@
+// A minimal RDB parser, mirroring the subset of the format `rdb_writer`
+// produces. It exists to back `--verify`: stream the writer's output back
+// through here and confirm the reconstructed keyspace matches the input.
+
+use super::rdb_writer::{
+    is_pair_array, value_as_f64, AUX_OPCODE, EOF_OPCODE, EXPIRETIME_MS_OPCODE, EXPIRETIME_OPCODE,
+    EXPIRE_AT_HINT_KEY, HASH_LISTPACK_TYPECODE, HASH_TYPECODE, LIST_TYPECODE, LIST_TYPE_HINT,
+    RDB_32BITLEN, RDB_64BITLEN, RDB_ENCVAL, RDB_ENC_INT16, RDB_ENC_INT32, RDB_ENC_INT8,
+    RDB_ENC_LZF, SELECTDB_OPCODE, SET_INTSET_TYPECODE, SET_TYPECODE, SET_TYPE_HINT,
+    STRING_TYPECODE, TTL_MS_HINT_KEY, TYPE_HINT_KEY, VALUES_HINT_KEY, ZSET_2_TYPECODE,
+    ZSET_TYPE_HINT,
+};
+use crate::types::toml_to_string::string_from_toml_value;
+use crc64::crc64;
+use toml::{Table, Value};
+
+#[derive(Debug, PartialEq)]
+pub enum RdbValue {
+    Str(String),
+    List(Vec<String>),
+    Set(Vec<String>),
+    Hash(Vec<(String, String)>),
+    ZSet(Vec<(String, f64)>),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct RdbEntry {
+    pub key: String,
+    pub value: RdbValue,
+}
+
+// Parses a full RDB byte stream (magic through the CRC64 footer) into its
+// key/value entries, validating the footer along the way.
+pub fn parse(bytes: &[u8]) -> Result<Vec<RdbEntry>, String> {
+    if bytes.len() < 9 + 8 || &bytes[0..5] != b"REDIS" {
+        return Err("not an RDB stream (bad magic)".to_string());
+    }
+
+    let mut pos = 9;
+    let mut entries = Vec::new();
+
+    loop {
+        if pos >= bytes.len() {
+            return Err("unexpected end of RDB stream before EOF opcode".to_string());
+        }
+        let opcode = bytes[pos];
+        pos += 1;
+
+        if opcode == EOF_OPCODE {
+            break;
+        } else if opcode == SELECTDB_OPCODE {
+            let (_, consumed) = decode_length(&bytes[pos..])?;
+            pos += consumed;
+        } else if opcode == AUX_OPCODE {
+            let (_, consumed) = decode_string(&bytes[pos..])?;
+            pos += consumed;
+            let (_, consumed) = decode_string(&bytes[pos..])?;
+            pos += consumed;
+        } else if opcode == EXPIRETIME_OPCODE {
+            pos += 4;
+        } else if opcode == EXPIRETIME_MS_OPCODE {
+            pos += 8;
+        } else {
+            let (key_bytes, consumed) = decode_string(&bytes[pos..])?;
+            pos += consumed;
+            let key = String::from_utf8(key_bytes).map_err(|e| e.to_string())?;
+            let (value, consumed) = decode_value(opcode, &bytes[pos..])?;
+            pos += consumed;
+            entries.push(RdbEntry { key, value });
+        }
+    }
+
+    let footer_start = bytes.len() - 8;
+    if pos != footer_start {
+        return Err("trailing bytes after EOF opcode".to_string());
+    }
+    let expected_crc = u64::from_le_bytes(bytes[footer_start..].try_into().unwrap());
+    let computed_crc = crc64(0, &bytes[..footer_start]);
+    if expected_crc != computed_crc {
+        return Err("CRC64 checksum mismatch".to_string());
+    }
+
+    Ok(entries)
+}
+
+fn decode_value(type_code: u8, data: &[u8]) -> Result<(RdbValue, usize), String> {
+    if type_code == STRING_TYPECODE {
+        let (bytes, consumed) = decode_string(data)?;
+        Ok((RdbValue::Str(to_utf8(bytes)?), consumed))
+    } else if type_code == LIST_TYPECODE {
+        let (members, consumed) = decode_string_list(data)?;
+        Ok((RdbValue::List(members), consumed))
+    } else if type_code == SET_TYPECODE {
+        let (members, consumed) = decode_string_list(data)?;
+        Ok((RdbValue::Set(members), consumed))
+    } else if type_code == HASH_TYPECODE {
+        let (count, mut pos) = decode_length(data)?;
+        let mut fields = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let (field, consumed) = decode_string(&data[pos..])?;
+            pos += consumed;
+            let (value, consumed) = decode_string(&data[pos..])?;
+            pos += consumed;
+            fields.push((to_utf8(field)?, to_utf8(value)?));
+        }
+        Ok((RdbValue::Hash(fields), pos))
+    } else if type_code == ZSET_2_TYPECODE {
+        let (count, mut pos) = decode_length(data)?;
+        let mut members = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let (member, consumed) = decode_string(&data[pos..])?;
+            pos += consumed;
+            let score = f64::from_le_bytes(data[pos..pos + 8].try_into().unwrap());
+            pos += 8;
+            members.push((to_utf8(member)?, score));
+        }
+        Ok((RdbValue::ZSet(members), pos))
+    } else if type_code == SET_INTSET_TYPECODE {
+        let (blob, consumed) = decode_string(data)?;
+        let members = decode_intset(&blob)
+            .into_iter()
+            .map(|value| value.to_string())
+            .collect();
+        Ok((RdbValue::Set(members), consumed))
+    } else if type_code == HASH_LISTPACK_TYPECODE {
+        let (blob, consumed) = decode_string(data)?;
+        let entries = decode_listpack(&blob)?;
+        let mut fields = Vec::with_capacity(entries.len() / 2);
+        let mut iter = entries.into_iter();
+        while let (Some(field), Some(value)) = (iter.next(), iter.next()) {
+            fields.push((to_utf8(field)?, to_utf8(value)?));
+        }
+        Ok((RdbValue::Hash(fields), consumed))
+    } else {
+        Err(format!("unsupported RDB type code {type_code:#04x}"))
+    }
+}
+
+fn decode_string_list(data: &[u8]) -> Result<(Vec<String>, usize), String> {
+    let (count, mut pos) = decode_length(data)?;
+    let mut members = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let (member, consumed) = decode_string(&data[pos..])?;
+        pos += consumed;
+        members.push(to_utf8(member)?);
+    }
+    Ok((members, pos))
+}
+
+fn to_utf8(bytes: Vec<u8>) -> Result<String, String> {
+    String::from_utf8(bytes).map_err(|e| e.to_string())
+}
+
+// Mirrors `rdb_writer::encode_string`: either a length-prefixed raw string
+// or a special (integer/LZF) encoding signaled by the top 2 bits.
+fn decode_string(data: &[u8]) -> Result<(Vec<u8>, usize), String> {
+    let marker = data[0];
+    if (marker >> 6) == RDB_ENCVAL {
+        let enctype = marker & 0x3F;
+        return if enctype == RDB_ENC_INT8 {
+            let value = data[1] as i8;
+            Ok((value.to_string().into_bytes(), 2))
+        } else if enctype == RDB_ENC_INT16 {
+            let value = i16::from_le_bytes(data[1..3].try_into().unwrap());
+            Ok((value.to_string().into_bytes(), 3))
+        } else if enctype == RDB_ENC_INT32 {
+            let value = i32::from_le_bytes(data[1..5].try_into().unwrap());
+            Ok((value.to_string().into_bytes(), 5))
+        } else if enctype == RDB_ENC_LZF {
+            let (compressed_len, c1) = decode_length(&data[1..])?;
+            let (uncompressed_len, c2) = decode_length(&data[1 + c1..])?;
+            let payload_start = 1 + c1 + c2;
+            let payload = &data[payload_start..payload_start + compressed_len as usize];
+            let decompressed = lzf_decompress(payload, uncompressed_len as usize);
+            Ok((decompressed, payload_start + compressed_len as usize))
+        } else {
+            Err(format!("unsupported string encoding {enctype:#04x}"))
+        };
+    }
+
+    let (length, consumed) = decode_length(data)?;
+    let length = length as usize;
+    Ok((data[consumed..consumed + length].to_vec(), consumed + length))
+}
+
+// Mirrors `rdb_writer::encode_length`.
+fn decode_length(data: &[u8]) -> Result<(u64, usize), String> {
+    let first = data[0];
+    if first == RDB_32BITLEN {
+        let length = u32::from_be_bytes(data[1..5].try_into().unwrap());
+        Ok((length as u64, 5))
+    } else if first == RDB_64BITLEN {
+        let length = u64::from_be_bytes(data[1..9].try_into().unwrap());
+        Ok((length, 9))
+    } else if (first >> 6) == 0 {
+        Ok(((first & 0x3F) as u64, 1))
+    } else if (first >> 6) == 1 {
+        let length = (((first & 0x3F) as u64) << 8) | data[1] as u64;
+        Ok((length, 2))
+    } else {
+        Err("length encoding used a special-string marker byte".to_string())
+    }
+}
+
+// Mirrors `rdb_writer::lzf_compress`'s control-byte scheme.
+fn lzf_decompress(data: &[u8], uncompressed_len: usize) -> Vec<u8> {
+    let mut output = Vec::with_capacity(uncompressed_len);
+    let mut pos = 0;
+    while pos < data.len() {
+        let ctrl = data[pos];
+        pos += 1;
+        let top3 = ctrl >> 5;
+        if top3 == 0 {
+            let len = (ctrl as usize) + 1;
+            output.extend_from_slice(&data[pos..pos + len]);
+            pos += len;
+        } else {
+            let mut len = (top3 as usize) + 2;
+            if top3 == 7 {
+                len = 9 + data[pos] as usize;
+                pos += 1;
+            }
+            let offset = (((ctrl & 0x1F) as usize) << 8 | data[pos] as usize) + 1;
+            pos += 1;
+            let start = output.len() - offset;
+            for i in 0..len {
+                let byte = output[start + i];
+                output.push(byte);
+            }
+        }
+    }
+    output
+}
+
+// Mirrors `rdb_writer::build_listpack`/`lp_encode_entry`: a 4-byte total
+// length, a 2-byte element count, then entries terminated by `0xFF`.
+fn decode_listpack(blob: &[u8]) -> Result<Vec<Vec<u8>>, String> {
+    let mut pos = 6;
+    let mut entries = Vec::new();
+    while blob[pos] != 0xFF {
+        let (entry, consumed) = lp_decode_entry(&blob[pos..])?;
+        entries.push(entry);
+        pos += consumed;
+    }
+    Ok(entries)
+}
+
+fn lp_decode_entry(data: &[u8]) -> Result<(Vec<u8>, usize), String> {
+    let marker = data[0];
+    let (value, data_len) = if marker & 0xC0 == 0x80 {
+        let len = (marker & 0x3F) as usize;
+        (data[1..1 + len].to_vec(), 1 + len)
+    } else if marker & 0xF0 == 0xE0 {
+        let len = (((marker & 0x0F) as usize) << 8) | data[1] as usize;
+        (data[2..2 + len].to_vec(), 2 + len)
+    } else if marker == 0xF0 {
+        let len = u32::from_le_bytes(data[1..5].try_into().unwrap()) as usize;
+        (data[5..5 + len].to_vec(), 5 + len)
+    } else {
+        return Err(format!("unsupported listpack entry encoding {marker:#04x}"));
+    };
+
+    Ok((value, data_len + lp_backlen_size(data_len)))
+}
+
+fn lp_backlen_size(len: usize) -> usize {
+    if len <= 127 {
+        1
+    } else if len < 16383 {
+        2
+    } else if len < 2_097_151 {
+        3
+    } else if len < 268_435_455 {
+        4
+    } else {
+        5
+    }
+}
+
+// Confirms a parsed RDB entry set reproduces the keyspace described by the
+// original TOML input. `original` is parsed independently (not reused from
+// the writer) so this is a genuine cross-check rather than comparing the
+// writer against itself.
+pub fn verify_against_toml(original: &str, entries: &[RdbEntry]) -> Result<(), String> {
+    let expected: Table = original.parse().map_err(|e: toml::de::Error| e.to_string())?;
+
+    let mut expected_keys: Vec<&String> = expected.keys().collect();
+    let mut actual_keys: Vec<&String> = entries.iter().map(|entry| &entry.key).collect();
+    expected_keys.sort();
+    actual_keys.sort();
+    if expected_keys != actual_keys {
+        return Err(format!(
+            "key mismatch: expected {expected_keys:?}, got {actual_keys:?}"
+        ));
+    }
+
+    for entry in entries {
+        let expected_value = expected.get(&entry.key).expect("key presence just checked");
+        verify_value(&entry.key, expected_value, &entry.value)?;
+    }
+    Ok(())
+}
+
+fn verify_value(key: &str, expected: &Value, actual: &RdbValue) -> Result<(), String> {
+    match expected {
+        Value::Array(array) if is_pair_array(array) => {
+            let RdbValue::ZSet(actual_members) = actual else {
+                return Err(format!("{key}: expected a zset, got {actual:?}"));
+            };
+            let mut expected_members: Vec<(String, f64)> = array
+                .iter()
+                .map(|pair| {
+                    let pair = pair.as_array().expect("validated by is_pair_array");
+                    let member = pair[0].as_str().expect("validated by is_pair_array");
+                    (member.to_string(), value_as_f64(&pair[1]))
+                })
+                .collect();
+            let mut actual_members = actual_members.clone();
+            expected_members.sort_by(|a, b| a.0.cmp(&b.0));
+            actual_members.sort_by(|a, b| a.0.cmp(&b.0));
+            if expected_members != actual_members {
+                return Err(format!(
+                    "{key}: zset mismatch, expected {expected_members:?}, got {actual_members:?}"
+                ));
+            }
+            Ok(())
+        }
+        Value::Array(array) => {
+            let mut expected_members: Vec<String> =
+                array.iter().map(string_from_toml_value).collect();
+            let mut actual_members = match actual {
+                RdbValue::Set(members) | RdbValue::List(members) => members.clone(),
+                _ => return Err(format!("{key}: expected a set/list, got {actual:?}")),
+            };
+            expected_members.sort();
+            actual_members.sort();
+            // A bare array always writes as a `RedisSet`, which dedups its
+            // members (see `build_intset`/`RedisSet::write_bytes`); match
+            // that here so e.g. `[1, 1, 2]` doesn't report a false mismatch.
+            if matches!(actual, RdbValue::Set(_)) {
+                expected_members.dedup();
+                actual_members.dedup();
+            }
+            if expected_members != actual_members {
+                return Err(format!(
+                    "{key}: set/list mismatch, expected {expected_members:?}, got {actual_members:?}"
+                ));
+            }
+            Ok(())
+        }
+        Value::Table(table) => verify_table_value(key, table, actual),
+        other => {
+            let expected_str = string_from_toml_value(other);
+            match actual {
+                RdbValue::Str(actual_str) if *actual_str == expected_str => Ok(()),
+                _ => Err(format!(
+                    "{key}: expected string {expected_str:?}, got {actual:?}"
+                )),
+            }
+        }
+    }
+}
+
+fn verify_table_value(key: &str, table: &Table, actual: &RdbValue) -> Result<(), String> {
+    let reserved = [
+        TYPE_HINT_KEY,
+        VALUES_HINT_KEY,
+        TTL_MS_HINT_KEY,
+        EXPIRE_AT_HINT_KEY,
+    ];
+    match table.get(TYPE_HINT_KEY).and_then(Value::as_str) {
+        Some(hint) if hint == ZSET_TYPE_HINT => {
+            let RdbValue::ZSet(actual_members) = actual else {
+                return Err(format!("{key}: expected a zset, got {actual:?}"));
+            };
+            let mut expected_members: Vec<(String, f64)> = table
+                .iter()
+                .filter(|(field, _)| !reserved.contains(&field.as_str()))
+                .map(|(field, value)| (field.clone(), value_as_f64(value)))
+                .collect();
+            let mut actual_members = actual_members.clone();
+            expected_members.sort_by(|a, b| a.0.cmp(&b.0));
+            actual_members.sort_by(|a, b| a.0.cmp(&b.0));
+            if expected_members != actual_members {
+                return Err(format!(
+                    "{key}: zset mismatch, expected {expected_members:?}, got {actual_members:?}"
+                ));
+            }
+            Ok(())
+        }
+        Some(hint) if hint == LIST_TYPE_HINT || hint == SET_TYPE_HINT => {
+            let values = table
+                .get(VALUES_HINT_KEY)
+                .and_then(Value::as_array)
+                .ok_or_else(|| format!("{key}: missing {VALUES_HINT_KEY}"))?;
+            let mut expected_members: Vec<String> =
+                values.iter().map(string_from_toml_value).collect();
+            let mut actual_members = match actual {
+                RdbValue::Set(members) | RdbValue::List(members) => members.clone(),
+                _ => return Err(format!("{key}: expected a set/list, got {actual:?}")),
+            };
+            expected_members.sort();
+            actual_members.sort();
+            // Same dedup as above: `__type__ = "set"` writes through
+            // `RedisSet`, which dedups members, so the comparison must too.
+            if hint == SET_TYPE_HINT {
+                expected_members.dedup();
+                actual_members.dedup();
+            }
+            if expected_members != actual_members {
+                return Err(format!(
+                    "{key}: set/list mismatch, expected {expected_members:?}, got {actual_members:?}"
+                ));
+            }
+            Ok(())
+        }
+        _ => {
+            let RdbValue::Hash(actual_fields) = actual else {
+                return Err(format!("{key}: expected a hash, got {actual:?}"));
+            };
+            let mut expected_fields: Vec<(String, String)> = table
+                .iter()
+                .filter(|(field, _)| !reserved.contains(&field.as_str()))
+                .map(|(field, value)| (field.clone(), string_from_toml_value(value)))
+                .collect();
+            let mut actual_fields = actual_fields.clone();
+            expected_fields.sort();
+            actual_fields.sort();
+            if expected_fields != actual_fields {
+                return Err(format!(
+                    "{key}: hash mismatch, expected {expected_fields:?}, got {actual_fields:?}"
+                ));
+            }
+            Ok(())
+        }
+    }
+}
+
+// Mirrors `rdb_writer::build_intset`.
+fn decode_intset(blob: &[u8]) -> Vec<i64> {
+    let encoding = u32::from_le_bytes(blob[0..4].try_into().unwrap());
+    let count = u32::from_le_bytes(blob[4..8].try_into().unwrap()) as usize;
+    let mut pos = 8;
+    let mut values = Vec::with_capacity(count);
+    for _ in 0..count {
+        let value = match encoding {
+            2 => {
+                let v = i16::from_le_bytes(blob[pos..pos + 2].try_into().unwrap()) as i64;
+                pos += 2;
+                v
+            }
+            4 => {
+                let v = i32::from_le_bytes(blob[pos..pos + 4].try_into().unwrap()) as i64;
+                pos += 4;
+                v
+            }
+            _ => {
+                let v = i64::from_le_bytes(blob[pos..pos + 8].try_into().unwrap());
+                pos += 8;
+                v
+            }
+        };
+        values.push(value);
+    }
+    values
+}
@@ -0,0 +1,951 @@
+use crate::types::toml_to_string::string_from_toml_value;
+use core::result::Result;
+use crc64::crc64;
+use regex::Regex;
+use std::io::{self, BufRead, Write};
+use toml::value::Array as TomlArray;
+use toml::{Table, Value};
+
+const TABLE_NAME_REGEX: &str = r"^\[\[?[^\s\[\]]+\]\]?";
+pub(crate) const STRING_TYPECODE: u8 = b'\x00';
+pub(crate) const LIST_TYPECODE: u8 = b'\x01';
+pub(crate) const SET_TYPECODE: u8 = b'\x02';
+pub(crate) const HASH_TYPECODE: u8 = b'\x04';
+pub(crate) const ZSET_2_TYPECODE: u8 = b'\x05';
+pub(crate) const SET_INTSET_TYPECODE: u8 = 11;
+pub(crate) const HASH_LISTPACK_TYPECODE: u8 = 16;
+
+// The RDB file-format version (the number in the `REDIS%04d` magic, see
+// `header`) in which upstream Redis introduced each compact-encoding
+// typecode. A loader reading a file declaring an older version will
+// reject a typecode newer than it, so the writer only reaches for a
+// compact encoding when the declared `REDIS_VERSION` supports it.
+const MIN_RDB_VERSION_FOR_INTSET: u8 = 3;
+const MIN_RDB_VERSION_FOR_HASH_LISTPACK: u8 = 10;
+
+pub(crate) const AUX_OPCODE: u8 = 0xFA;
+pub(crate) const SELECTDB_OPCODE: u8 = 0xFE;
+pub(crate) const EOF_OPCODE: u8 = 0xFF;
+
+// Reserved keys that let a TOML table opt into a Redis type other than
+// the one its shape would otherwise imply (hash for tables, set for
+// arrays). `__type__` names the type; `__values__` carries the payload
+// for types (list, set) that aren't naturally a table of member/score
+// pairs.
+pub(crate) const TYPE_HINT_KEY: &str = "__type__";
+pub(crate) const VALUES_HINT_KEY: &str = "__values__";
+pub(crate) const ZSET_TYPE_HINT: &str = "zset";
+pub(crate) const LIST_TYPE_HINT: &str = "list";
+pub(crate) const SET_TYPE_HINT: &str = "set";
+
+// Reserved keys that attach an expiration to the key they share a
+// table with. `__expire_at__` is an absolute unix-seconds timestamp;
+// `__ttl_ms__` is milliseconds from now, resolved to an absolute
+// timestamp at write time.
+pub(crate) const EXPIRE_AT_HINT_KEY: &str = "__expire_at__";
+pub(crate) const TTL_MS_HINT_KEY: &str = "__ttl_ms__";
+pub(crate) const EXPIRETIME_MS_OPCODE: u8 = 0xFC;
+pub(crate) const EXPIRETIME_OPCODE: u8 = 0xFD;
+
+// First 2 bits of the length-encoding bytes are reserved for
+// one of these constants, telling Redis how many bytes in total
+// will be representing the length of the coming value
+pub(crate) const RDB_6BITLEN: u8 = 0;
+pub(crate) const RDB_14BITLEN: u8 = 1;
+pub(crate) const RDB_32BITLEN: u8 = 0x80;
+pub(crate) const RDB_64BITLEN: u8 = 0x81;
+
+// Top 2 bits set (0xC0) on a length byte signal a "special" encoding
+// rather than a length-prefixed raw string. The bottom 6 bits name
+// which encoding via one of the RDB_ENC_* constants below.
+pub(crate) const RDB_ENCVAL: u8 = 3;
+pub(crate) const RDB_ENC_INT8: u8 = 0;
+pub(crate) const RDB_ENC_INT16: u8 = 1;
+pub(crate) const RDB_ENC_INT32: u8 = 2;
+pub(crate) const RDB_ENC_LZF: u8 = 3;
+
+// LZF compression is skipped below this length: the 1-3 byte header
+// overhead isn't worth it for tiny strings.
+const LZF_MIN_INPUT_LEN: usize = 20;
+const LZF_HASH_BITS: u32 = 13;
+const LZF_MAX_LITERAL: usize = 1 << 5;
+const LZF_MAX_OFF: usize = 1 << 13;
+const LZF_MAX_REF: usize = (1 << 8) + (1 << 3);
+
+struct RedisHash<'a> {
+    key: &'a String,
+    value: &'a Table,
+    crc: u64,
+}
+
+struct RedisSet<'a> {
+    key: &'a String,
+    value: &'a TomlArray,
+    crc: u64,
+}
+
+struct RedisString<'a> {
+    key: &'a String,
+    value: &'a String,
+    crc: u64,
+}
+
+struct RedisList<'a> {
+    key: &'a String,
+    value: &'a TomlArray,
+    crc: u64,
+}
+
+// A sorted set's members can come from either a table of
+// `member = score` pairs or an array of `[member, score]` pairs, so the
+// source is kept generic over both shapes rather than duplicating the
+// writer.
+enum ZSetMembers<'a> {
+    Table(&'a Table),
+    Pairs(&'a TomlArray),
+}
+
+struct RedisZSet<'a> {
+    key: &'a String,
+    value: ZSetMembers<'a>,
+    crc: u64,
+}
+
+trait RedisWriter {
+    fn write_bytes(&mut self, buf_writer: &mut impl Write);
+}
+
+impl RedisWriter for RedisHash<'_> {
+    fn write_bytes(&mut self, buf_writer: &mut impl Write) {
+        if let Some(listpack) = try_encode_hash_listpack(self.value) {
+            self.crc = checksum_write(
+                buf_writer,
+                &[
+                    &[HASH_LISTPACK_TYPECODE],
+                    &encode_string(self.key)[..],
+                    &encode_raw_bytes(&listpack)[..],
+                ]
+                .concat(),
+                self.crc,
+            );
+            return;
+        }
+
+        let mut table_bytes = [&[HASH_TYPECODE], &encode_string(self.key)[..]].concat();
+        let mut table_length_encoding: Vec<u8> =
+            encode_length(self.value.len().try_into().unwrap());
+        table_bytes.append(&mut table_length_encoding);
+
+        for (key, val) in self.value.into_iter() {
+            table_bytes.append(&mut encode_string(key));
+            table_bytes.append(&mut encode_string(&string_from_toml_value(val)));
+        }
+        self.crc = checksum_write(buf_writer, &table_bytes, self.crc);
+    }
+}
+
+impl RedisWriter for RedisString<'_> {
+    fn write_bytes(&mut self, buf_writer: &mut impl Write) {
+        self.crc = checksum_write(
+            buf_writer,
+            &[
+                &[STRING_TYPECODE],
+                &encode_string(self.key)[..],
+                &encode_string(self.value)[..],
+            ]
+            .concat(),
+            self.crc,
+        );
+    }
+}
+
+impl RedisWriter for RedisSet<'_> {
+    fn write_bytes(&mut self, buf_writer: &mut impl Write) {
+        if let Some(intset) = try_encode_set_intset(self.value) {
+            self.crc = checksum_write(
+                buf_writer,
+                &[
+                    &[SET_INTSET_TYPECODE],
+                    &encode_string(self.key)[..],
+                    &encode_raw_bytes(&intset)[..],
+                ]
+                .concat(),
+                self.crc,
+            );
+            return;
+        }
+
+        let mut members: Vec<String> = self.value.iter().map(string_from_toml_value).collect();
+        members.sort_unstable();
+        members.dedup();
+
+        let mut set_bytes: Vec<u8> = [&[SET_TYPECODE], &encode_string(self.key)[..]].concat();
+        let mut set_length_encoding: Vec<u8> = encode_length(members.len().try_into().unwrap());
+        set_bytes.append(&mut set_length_encoding);
+
+        for member in &members {
+            set_bytes.append(&mut encode_string(member));
+        }
+        self.crc = checksum_write(buf_writer, &set_bytes, self.crc);
+    }
+}
+
+impl RedisWriter for RedisList<'_> {
+    fn write_bytes(&mut self, buf_writer: &mut impl Write) {
+        let mut list_bytes: Vec<u8> = [&[LIST_TYPECODE], &encode_string(self.key)[..]].concat();
+        let mut list_length_encoding: Vec<u8> =
+            encode_length(self.value.len().try_into().unwrap());
+        list_bytes.append(&mut list_length_encoding);
+
+        for val in self.value {
+            list_bytes.append(&mut encode_string(&string_from_toml_value(val)));
+        }
+        self.crc = checksum_write(buf_writer, &list_bytes, self.crc);
+    }
+}
+
+impl RedisWriter for RedisZSet<'_> {
+    fn write_bytes(&mut self, buf_writer: &mut impl Write) {
+        let members: Vec<(String, f64)> = match self.value {
+            ZSetMembers::Table(table) => table
+                .into_iter()
+                .map(|(member, score)| (member.clone(), value_as_f64(score)))
+                .collect(),
+            ZSetMembers::Pairs(pairs) => pairs
+                .iter()
+                .map(|pair| {
+                    let pair = pair.as_array().expect(crate::INVALID_TOML_ERROR);
+                    let member = pair
+                        .first()
+                        .and_then(Value::as_str)
+                        .expect(crate::INVALID_TOML_ERROR);
+                    let score = pair.get(1).expect(crate::INVALID_TOML_ERROR);
+                    (member.to_string(), value_as_f64(score))
+                })
+                .collect(),
+        };
+
+        let mut zset_bytes: Vec<u8> =
+            [&[ZSET_2_TYPECODE], &encode_string(self.key)[..]].concat();
+        let mut zset_length_encoding: Vec<u8> =
+            encode_length(members.len().try_into().unwrap());
+        zset_bytes.append(&mut zset_length_encoding);
+
+        for (member, score) in &members {
+            zset_bytes.append(&mut encode_string(member));
+            zset_bytes.extend_from_slice(&score.to_le_bytes());
+        }
+        self.crc = checksum_write(buf_writer, &zset_bytes, self.crc);
+    }
+}
+
+pub(crate) fn value_as_f64(value: &Value) -> f64 {
+    match value {
+        Value::Integer(integer_value) => *integer_value as f64,
+        Value::Float(float_value) => *float_value,
+        _ => string_from_toml_value(value)
+            .parse()
+            .expect(crate::INVALID_TOML_ERROR),
+    }
+}
+
+// Wraps an already-encoded blob (a listpack/intset payload) as a plain
+// RDB string: just the length prefix, no integer/LZF special encoding.
+fn encode_raw_bytes(bytes: &[u8]) -> Vec<u8> {
+    [
+        encode_length(bytes.len().try_into().unwrap()),
+        bytes.to_vec(),
+    ]
+    .concat()
+}
+
+// If `table` is small enough (element count and value size both under
+// the configured thresholds), packs it into a single listpack blob
+// instead of the per-field `HASH_TYPECODE` encoding.
+fn try_encode_hash_listpack(table: &Table) -> Option<Vec<u8>> {
+    let redis_version = *crate::REDIS_VERSION.get().expect("Redis version is not set");
+    if redis_version < MIN_RDB_VERSION_FOR_HASH_LISTPACK {
+        return None;
+    }
+
+    let max_entries = crate::env::environment::get_hash_max_listpack_entries();
+    let max_value_len = crate::env::environment::get_hash_max_listpack_value();
+    if table.len() as u64 > max_entries {
+        return None;
+    }
+
+    let mut entries: Vec<Vec<u8>> = Vec::with_capacity(table.len() * 2);
+    for (field, val) in table {
+        let value_str = string_from_toml_value(val);
+        if field.len() as u64 > max_value_len || value_str.len() as u64 > max_value_len {
+            return None;
+        }
+        entries.push(field.as_bytes().to_vec());
+        entries.push(value_str.into_bytes());
+    }
+    Some(build_listpack(&entries))
+}
+
+// If every element of `array` is an integer and the element count is
+// under the configured threshold, packs it into a single intset blob
+// instead of the per-element `SET_TYPECODE` encoding.
+fn try_encode_set_intset(array: &TomlArray) -> Option<Vec<u8>> {
+    let redis_version = *crate::REDIS_VERSION.get().expect("Redis version is not set");
+    if redis_version < MIN_RDB_VERSION_FOR_INTSET {
+        return None;
+    }
+
+    let max_entries = crate::env::environment::get_set_max_intset_entries();
+    if array.is_empty() || array.len() as u64 > max_entries {
+        return None;
+    }
+    array
+        .iter()
+        .map(|val| val.as_integer())
+        .collect::<Option<Vec<i64>>>()
+        .map(build_intset)
+}
+
+// Redis's "listpack" format: a 4-byte total-length header, a 2-byte
+// element count, the entries themselves (each a value followed by its
+// own length for backward traversal), then a single `0xFF` terminator.
+fn build_listpack(entries: &[Vec<u8>]) -> Vec<u8> {
+    let mut body = Vec::new();
+    for entry in entries {
+        body.append(&mut lp_encode_entry(entry));
+    }
+    body.push(0xFF);
+
+    let total_len: u32 = (6 + body.len()).try_into().unwrap();
+    let num_elements = entries.len().min(u16::MAX as usize) as u16;
+
+    let mut listpack = Vec::with_capacity(total_len as usize);
+    listpack.extend_from_slice(&total_len.to_le_bytes());
+    listpack.extend_from_slice(&num_elements.to_le_bytes());
+    listpack.extend_from_slice(&body);
+    listpack
+}
+
+// One listpack entry: a small-string/12-bit-string/32-bit-string
+// encoding of `value`, followed by a "backlen" recording the entry's
+// own byte length so the listpack can be walked in either direction.
+fn lp_encode_entry(value: &[u8]) -> Vec<u8> {
+    let mut entry = lp_encode_string(value);
+    entry.append(&mut lp_encode_backlen(entry.len()));
+    entry
+}
+
+fn lp_encode_string(value: &[u8]) -> Vec<u8> {
+    let len = value.len();
+    if len <= 63 {
+        [&[0x80 | (len as u8)][..], value].concat()
+    } else if len < 4096 {
+        [&[0xE0 | ((len >> 8) as u8), (len & 0xFF) as u8][..], value].concat()
+    } else {
+        [&[0xF0][..], &(len as u32).to_le_bytes()[..], value].concat()
+    }
+}
+
+fn lp_encode_backlen(len: usize) -> Vec<u8> {
+    let len = len as u64;
+    if len <= 127 {
+        vec![len as u8]
+    } else if len < 16383 {
+        vec![(len >> 7) as u8, ((len & 127) | 128) as u8]
+    } else if len < 2_097_151 {
+        vec![
+            (len >> 14) as u8,
+            (((len >> 7) & 127) | 128) as u8,
+            ((len & 127) | 128) as u8,
+        ]
+    } else if len < 268_435_455 {
+        vec![
+            (len >> 21) as u8,
+            (((len >> 14) & 127) | 128) as u8,
+            (((len >> 7) & 127) | 128) as u8,
+            ((len & 127) | 128) as u8,
+        ]
+    } else {
+        vec![
+            (len >> 28) as u8,
+            (((len >> 21) & 127) | 128) as u8,
+            (((len >> 14) & 127) | 128) as u8,
+            (((len >> 7) & 127) | 128) as u8,
+            ((len & 127) | 128) as u8,
+        ]
+    }
+}
+
+// Redis's "intset": a 4-byte encoding width (2/4/8), a 4-byte element
+// count, then the values themselves sorted ascending at that fixed
+// little-endian width.
+fn build_intset(mut values: Vec<i64>) -> Vec<u8> {
+    values.sort_unstable();
+    values.dedup();
+    let encoding: u32 = if values
+        .iter()
+        .all(|&v| v >= i16::MIN as i64 && v <= i16::MAX as i64)
+    {
+        2
+    } else if values
+        .iter()
+        .all(|&v| v >= i32::MIN as i64 && v <= i32::MAX as i64)
+    {
+        4
+    } else {
+        8
+    };
+
+    let mut intset = Vec::with_capacity(8 + values.len() * encoding as usize);
+    intset.extend_from_slice(&encoding.to_le_bytes());
+    intset.extend_from_slice(&(values.len() as u32).to_le_bytes());
+    for value in values {
+        match encoding {
+            2 => intset.extend_from_slice(&(value as i16).to_le_bytes()),
+            4 => intset.extend_from_slice(&(value as i32).to_le_bytes()),
+            _ => intset.extend_from_slice(&value.to_le_bytes()),
+        }
+    }
+    intset
+}
+
+fn encode_length(length: u64) -> Vec<u8> {
+    if length < (1 << 6) {
+        Vec::<u8>::from([u8::try_from(length).unwrap() | (RDB_6BITLEN << 6)])
+    } else if length < (1 << 14) {
+        Vec::<u8>::from([
+            u8::try_from(length >> 8).unwrap() | (RDB_14BITLEN << 6),
+            u8::try_from(length & 0xFF).unwrap(),
+        ])
+    } else if length < u64::from(u32::MAX) {
+        [
+            &[RDB_32BITLEN],
+            &u32::try_from(length).unwrap().to_be_bytes()[..],
+        ]
+        .concat()
+    } else {
+        [&[RDB_64BITLEN], &length.to_be_bytes()[..]].concat()
+    }
+}
+
+fn checksum_write(buf_writer: &mut impl Write, bytes: &[u8], start_crc: u64) -> u64 {
+    let _ = buf_writer.write(bytes);
+    crc64(start_crc, bytes)
+}
+
+fn encode_string(value: &String) -> Vec<u8> {
+    if let Some(encoded) = encode_integer_string(value) {
+        return encoded;
+    }
+    let raw = value.as_bytes();
+    if raw.len() >= LZF_MIN_INPUT_LEN {
+        if let Some(compressed) = encode_lzf_string(raw) {
+            return compressed;
+        }
+    }
+    [encode_length(value.len().try_into().unwrap()), raw.to_vec()].concat()
+}
+
+// Mirrors Redis' RDB_ENC_INT8/16/32: a string that is itself the
+// canonical decimal form of a small integer is stored as that integer
+// in little-endian instead of as ASCII digits.
+fn encode_integer_string(value: &str) -> Option<Vec<u8>> {
+    let parsed: i64 = value.parse().ok()?;
+    if parsed.to_string() != value {
+        // Reject non-canonical forms (leading zeros, "+5", etc.) since
+        // decoding them back would not reproduce the original string.
+        return None;
+    }
+
+    if let Ok(int8) = i8::try_from(parsed) {
+        Some(vec![(RDB_ENCVAL << 6) | RDB_ENC_INT8, int8 as u8])
+    } else if let Ok(int16) = i16::try_from(parsed) {
+        let bytes = int16.to_le_bytes();
+        Some([&[(RDB_ENCVAL << 6) | RDB_ENC_INT16], &bytes[..]].concat())
+    } else if let Ok(int32) = i32::try_from(parsed) {
+        let bytes = int32.to_le_bytes();
+        Some([&[(RDB_ENCVAL << 6) | RDB_ENC_INT32], &bytes[..]].concat())
+    } else {
+        None
+    }
+}
+
+// Mirrors Redis' RDB_ENC_LZF: `0xC3` followed by the RDB-length-encoded
+// compressed length, the RDB-length-encoded original length, then the
+// compressed bytes themselves. Falls back to `None` (raw encoding)
+// whenever LZF does not actually shrink the input.
+fn encode_lzf_string(raw: &[u8]) -> Option<Vec<u8>> {
+    let compressed = lzf_compress(raw)?;
+    if compressed.len() >= raw.len() {
+        return None;
+    }
+    Some(
+        [
+            &[(RDB_ENCVAL << 6) | RDB_ENC_LZF][..],
+            &encode_length(compressed.len().try_into().unwrap())[..],
+            &encode_length(raw.len().try_into().unwrap())[..],
+            &compressed[..],
+        ]
+        .concat(),
+    )
+}
+
+// A small from-scratch LZF compressor: a rolling hash over the next 3
+// bytes maps to the last position that 3-byte sequence was seen, and we
+// emit either a literal run (ctrl byte `n - 1` followed by `n` raw
+// bytes) or a back-reference into the last 8KiB of output whenever a
+// match of 3+ bytes is found. Returns `None` if the input is too short
+// to usefully hash.
+fn lzf_compress(input: &[u8]) -> Option<Vec<u8>> {
+    if input.len() < 4 {
+        return None;
+    }
+
+    let mut hash_table: Vec<Option<usize>> = vec![None; 1 << LZF_HASH_BITS];
+    let mut output: Vec<u8> = Vec::with_capacity(input.len());
+    let mut literal_run: Vec<u8> = Vec::new();
+    let mut pos = 0usize;
+
+    while pos + 2 < input.len() {
+        let hash = lzf_hash(&input[pos..pos + 3]);
+        let candidate = hash_table[hash];
+        hash_table[hash] = Some(pos);
+
+        let match_len = candidate.and_then(|cand| {
+            if pos - cand > LZF_MAX_OFF {
+                return None;
+            }
+            let max_len = LZF_MAX_REF.min(input.len() - pos);
+            let len = (0..max_len)
+                .take_while(|&i| input[cand + i] == input[pos + i])
+                .count();
+            if len >= 3 {
+                Some((cand, len))
+            } else {
+                None
+            }
+        });
+
+        match match_len {
+            Some((cand, len)) => {
+                flush_literal_run(&mut output, &mut literal_run);
+                write_lzf_backref(&mut output, pos - cand, len);
+                for i in 1..len {
+                    if pos + i + 2 < input.len() {
+                        hash_table[lzf_hash(&input[pos + i..pos + i + 3])] = Some(pos + i);
+                    }
+                }
+                pos += len;
+            }
+            None => {
+                literal_run.push(input[pos]);
+                if literal_run.len() == LZF_MAX_LITERAL {
+                    flush_literal_run(&mut output, &mut literal_run);
+                }
+                pos += 1;
+            }
+        }
+    }
+    for &byte in &input[pos..] {
+        literal_run.push(byte);
+        if literal_run.len() == LZF_MAX_LITERAL {
+            flush_literal_run(&mut output, &mut literal_run);
+        }
+    }
+    flush_literal_run(&mut output, &mut literal_run);
+
+    Some(output)
+}
+
+fn lzf_hash(bytes: &[u8]) -> usize {
+    let value = (bytes[0] as usize) << 16 | (bytes[1] as usize) << 8 | bytes[2] as usize;
+    ((value >> (24 - LZF_HASH_BITS)) ^ value) & ((1 << LZF_HASH_BITS) - 1)
+}
+
+fn flush_literal_run(output: &mut Vec<u8>, literal_run: &mut Vec<u8>) {
+    if literal_run.is_empty() {
+        return;
+    }
+    output.push((literal_run.len() - 1) as u8);
+    output.extend_from_slice(literal_run);
+    literal_run.clear();
+}
+
+fn write_lzf_backref(output: &mut Vec<u8>, offset: usize, len: usize) {
+    let offset = offset - 1;
+    let mut remaining_len = len - 2;
+    if remaining_len < 7 {
+        output.push(((remaining_len as u8) << 5) | ((offset >> 8) as u8));
+    } else {
+        output.push((7 << 5) | ((offset >> 8) as u8));
+        remaining_len -= 7;
+        output.push(remaining_len as u8);
+    }
+    output.push((offset & 0xFF) as u8);
+}
+
+// A key's expiration, resolved to the absolute timestamp form the RDB
+// expire opcodes expect.
+enum Expiry {
+    Seconds(u32),
+    Milliseconds(u64),
+}
+
+fn write_expiry(buf_writer: &mut impl Write, expiry: &Expiry, crc: u64) -> u64 {
+    match expiry {
+        Expiry::Seconds(secs) => checksum_write(
+            buf_writer,
+            &[&[EXPIRETIME_OPCODE], &secs.to_le_bytes()[..]].concat(),
+            crc,
+        ),
+        Expiry::Milliseconds(millis) => checksum_write(
+            buf_writer,
+            &[&[EXPIRETIME_MS_OPCODE], &millis.to_le_bytes()[..]].concat(),
+            crc,
+        ),
+    }
+}
+
+// Strips and resolves a per-key expiry hint from a table in place, so
+// the remaining entries can be serialized without the reserved keys
+// leaking through as hash/zset members.
+fn extract_expiry(table: &mut Table) -> Option<Expiry> {
+    let expire_at = table.remove(EXPIRE_AT_HINT_KEY);
+    let ttl_ms = table.remove(TTL_MS_HINT_KEY);
+    if let Some(Value::Integer(secs)) = expire_at {
+        return Some(Expiry::Seconds(
+            u32::try_from(secs).expect(crate::INVALID_TOML_ERROR),
+        ));
+    }
+    if let Some(Value::Integer(ttl_ms)) = ttl_ms {
+        let ttl_ms = u64::try_from(ttl_ms).expect(crate::INVALID_TOML_ERROR);
+        return Some(Expiry::Milliseconds(
+            crate::env::environment::now_unix_millis() + ttl_ms,
+        ));
+    }
+    None
+}
+
+// Falls back to a global default expiry (set via the `environment`
+// module) for keys that don't carry their own hint.
+fn default_expiry() -> Option<Expiry> {
+    if let Some(secs) = crate::env::environment::get_default_expire_at() {
+        return Some(Expiry::Seconds(secs));
+    }
+    if let Some(ttl_ms) = crate::env::environment::get_default_ttl_ms() {
+        return Some(Expiry::Milliseconds(
+            crate::env::environment::now_unix_millis() + ttl_ms,
+        ));
+    }
+    None
+}
+
+// An array of two-element `[member, score]` arrays is unambiguously a
+// sorted set, so it's auto-detected without needing a `__type__` hint.
+pub(crate) fn is_pair_array(array: &TomlArray) -> bool {
+    !array.is_empty()
+        && array.iter().all(|element| {
+            matches!(element.as_array(), Some(pair) if pair.len() == 2 && pair[0].is_str())
+        })
+}
+
+fn write_to_rdb_bytes_from_string(
+    buf_writer: &mut impl Write,
+    key_value_string: String,
+    crc: u64,
+) -> u64 {
+    let table: Table = key_value_string
+        .parse::<Table>()
+        .expect(crate::INVALID_TOML_ERROR);
+    let key = table.keys().next().expect(crate::INVALID_TOML_ERROR);
+
+    // Own the value so a per-key `__expire_at__`/`__ttl_ms__` hint can
+    // be stripped out of it before the remaining fields are written.
+    let mut value = table.get(key).expect(crate::INVALID_TOML_ERROR).clone();
+    let expiry = match &mut value {
+        Value::Table(inner) => extract_expiry(inner).or_else(default_expiry),
+        _ => default_expiry(),
+    };
+
+    let mut crc = crc;
+    if let Some(expiry) = &expiry {
+        crc = write_expiry(buf_writer, expiry, crc);
+    }
+
+    match &value {
+        Value::Array(array_val) if is_pair_array(array_val) => {
+            let mut zset = RedisZSet {
+                key,
+                value: ZSetMembers::Pairs(array_val),
+                crc,
+            };
+            zset.write_bytes(buf_writer);
+            zset.crc
+        }
+        Value::Array(array_val) => {
+            let mut set = RedisSet {
+                key,
+                value: array_val,
+                crc,
+            };
+            set.write_bytes(buf_writer);
+            set.crc
+        }
+        Value::Table(table_val) => match table_val.get(TYPE_HINT_KEY).and_then(Value::as_str) {
+            Some(ZSET_TYPE_HINT) => {
+                let mut members = table_val.clone();
+                members.remove(TYPE_HINT_KEY);
+                let mut zset = RedisZSet {
+                    key,
+                    value: ZSetMembers::Table(&members),
+                    crc,
+                };
+                zset.write_bytes(buf_writer);
+                zset.crc
+            }
+            Some(LIST_TYPE_HINT) => {
+                let values = table_val
+                    .get(VALUES_HINT_KEY)
+                    .and_then(Value::as_array)
+                    .expect(crate::INVALID_TOML_ERROR);
+                let mut list = RedisList {
+                    key,
+                    value: values,
+                    crc,
+                };
+                list.write_bytes(buf_writer);
+                list.crc
+            }
+            Some(SET_TYPE_HINT) => {
+                let values = table_val
+                    .get(VALUES_HINT_KEY)
+                    .and_then(Value::as_array)
+                    .expect(crate::INVALID_TOML_ERROR);
+                let mut set = RedisSet {
+                    key,
+                    value: values,
+                    crc,
+                };
+                set.write_bytes(buf_writer);
+                set.crc
+            }
+            _ => {
+                let mut hash = RedisHash {
+                    key,
+                    value: table_val,
+                    crc,
+                };
+                hash.write_bytes(buf_writer);
+                hash.crc
+            }
+        },
+        other_val => {
+            let mut redis_str = RedisString {
+                key,
+                value: &string_from_toml_value(other_val),
+                crc,
+            };
+            redis_str.write_bytes(buf_writer);
+            redis_str.crc
+        }
+    }
+}
+
+// Tracks the table currently being accumulated. A table stays
+// `Incomplete` across any number of lines (blank lines included, since
+// those are just part of a TOML table body) until a header naming a
+// *different* top-level key, or EOF, confirms it's done and flushes it.
+// `top_level_key` lets repeated `[[array.of.tables]]` blocks and a
+// `[parent.child]` subtable that follows its `[parent]` header stay in
+// the same accumulation instead of being split into separate, invalid
+// top-level tables.
+enum TableAccumulator {
+    Empty,
+    Incomplete {
+        contents: String,
+        top_level_key: String,
+    },
+}
+
+// The key a `[table]`/`[[table]]` header line introduces at the
+// top level, e.g. `[[a]]` and `[a.b]` both name `a`. Used to decide
+// whether a header continues the table currently being accumulated
+// (same top-level key) or starts a new one.
+fn top_level_table_key(header: &str) -> &str {
+    header
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .split('.')
+        .next()
+        .unwrap_or(header)
+}
+
+// How much `line` changes the nesting depth of an open array/inline-table
+// value (e.g. a `matrix = [` that continues across several lines), so the
+// splitter can tell a value-continuation line that happens to start with
+// `[` (such as a row of a multiline array) apart from an actual table
+// header. Brackets inside quoted strings and `#` comments don't count.
+fn bracket_depth_delta(line: &str) -> i32 {
+    let mut delta = 0;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '#' => break,
+            '\'' => {
+                for next in chars.by_ref() {
+                    if next == '\'' {
+                        break;
+                    }
+                }
+            }
+            '"' => {
+                let mut escaped = false;
+                for next in chars.by_ref() {
+                    if escaped {
+                        escaped = false;
+                    } else if next == '\\' {
+                        escaped = true;
+                    } else if next == '"' {
+                        break;
+                    }
+                }
+            }
+            '[' | '{' => delta += 1,
+            ']' | '}' => delta -= 1,
+            _ => {}
+        }
+    }
+    delta
+}
+
+pub fn rdb_from_buffer<R: io::Read>(
+    buf_reader: &mut io::BufReader<R>,
+    buf_writer: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut crc: u64 = 0;
+    crc = header(buf_writer, crc);
+    let table_name_regex: Regex = Regex::new(TABLE_NAME_REGEX).unwrap();
+    let mut table_accumulator = TableAccumulator::Empty;
+    // >0 while inside a value (array/inline-table) left open by a prior
+    // line; lines seen in that state can never be a new table header,
+    // no matter what character they start with.
+    let mut open_value_depth: i32 = 0;
+
+    for line in buf_reader.lines() {
+        let line_str: String = line.expect(crate::INVALID_TOML_ERROR);
+        let inside_open_value = open_value_depth > 0;
+        open_value_depth = (open_value_depth + bracket_depth_delta(&line_str)).max(0);
+
+        if !inside_open_value {
+            if let Some(header_match) = table_name_regex.find(&line_str) {
+                let header_key = top_level_table_key(header_match.as_str()).to_string();
+                let continues_current = matches!(
+                    &table_accumulator,
+                    TableAccumulator::Incomplete { top_level_key, .. } if *top_level_key == header_key
+                );
+
+                if !continues_current {
+                    if let TableAccumulator::Incomplete { contents, .. } =
+                        std::mem::replace(&mut table_accumulator, TableAccumulator::Empty)
+                    {
+                        crc = write_to_rdb_bytes_from_string(buf_writer, contents, crc);
+                    }
+                    table_accumulator = TableAccumulator::Incomplete {
+                        contents: String::new(),
+                        top_level_key: header_key,
+                    };
+                }
+
+                if let TableAccumulator::Incomplete { contents, .. } = &mut table_accumulator {
+                    if !contents.is_empty() {
+                        contents.push('\n');
+                    }
+                    contents.push_str(&line_str);
+                }
+                continue;
+            }
+        }
+
+        match &mut table_accumulator {
+            TableAccumulator::Incomplete { contents, .. } => {
+                contents.push('\n');
+                contents.push_str(&line_str);
+            }
+            TableAccumulator::Empty if !line_str.is_empty() => {
+                crc = write_to_rdb_bytes_from_string(buf_writer, line_str, crc);
+            }
+            TableAccumulator::Empty => {}
+        }
+    }
+
+    if let TableAccumulator::Incomplete { contents, .. } = table_accumulator {
+        crc = write_to_rdb_bytes_from_string(buf_writer, contents, crc);
+    }
+    end_of_file(buf_writer, crc);
+    Ok(())
+}
+
+fn header(buf_writer: &mut impl Write, crc: u64) -> u64 {
+    let mut crc = checksum_write(
+        buf_writer,
+        &format!(
+            "REDIS{:04}",
+            crate::REDIS_VERSION
+                .get()
+                .expect("Redis version is not set")
+        )
+        .into_bytes(),
+        crc,
+    );
+
+    crc = write_aux_field(
+        buf_writer,
+        "redis-ver",
+        &crate::env::environment::get_redis_version_string(),
+        crc,
+    );
+    crc = write_aux_field(
+        buf_writer,
+        "redis-bits",
+        &crate::REDIS_BITS.get().expect("redis-bits is not set").to_string(),
+        crc,
+    );
+    crc = write_aux_field(
+        buf_writer,
+        "ctime",
+        &crate::REDIS_CTIME.get().expect("ctime is not set").to_string(),
+        crc,
+    );
+    crc = write_aux_field(
+        buf_writer,
+        "used-mem",
+        &crate::REDIS_USED_MEM.get().expect("used-mem is not set").to_string(),
+        crc,
+    );
+
+    checksum_write(
+        buf_writer,
+        &[SELECTDB_OPCODE, 0x00], // ID of the database = 0
+        crc,
+    )
+}
+
+// Writes one aux-field opcode followed by two RDB-encoded strings: the
+// field name and its value.
+fn write_aux_field(buf_writer: &mut impl Write, name: &str, value: &str, crc: u64) -> u64 {
+    checksum_write(
+        buf_writer,
+        &[
+            &[AUX_OPCODE],
+            &encode_string(&name.to_string())[..],
+            &encode_string(&value.to_string())[..],
+        ]
+        .concat(),
+        crc,
+    )
+}
+
+fn end_of_file(buf_writer: &mut impl Write, crc: u64) {
+    let final_checksum = checksum_write(buf_writer, &[EOF_OPCODE], crc).to_le_bytes();
+    let _ = buf_writer.write(&final_checksum);
+}
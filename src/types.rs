@@ -8,6 +8,13 @@ pub mod toml_to_string {
             Value::Float(float_value) => float_value.to_string(),
             Value::Boolean(boolean_value) => boolean_value.to_string(),
             Value::Datetime(datetime_value) => datetime_value.to_string(),
+            // A nested table or array (an array-of-tables member, a
+            // dotted subtable, a 2D array, ...) has no scalar Redis
+            // representation. Name the shape explicitly rather than
+            // falling through to the bare `INVALID_TOML_ERROR` panic
+            // below.
+            Value::Table(_) => panic!("{}: nested table", crate::INVALID_TOML_ERROR),
+            Value::Array(_) => panic!("{}: nested array", crate::INVALID_TOML_ERROR),
             other => other.as_str().expect(crate::INVALID_TOML_ERROR).to_string(),
         }
     }